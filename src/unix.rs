@@ -5,7 +5,7 @@
 //! semaphore is generally easier to use, but it does not relinquish resources
 //! when a process terminates unexpectedly. On the other ahnd a System V
 //! semaphore provides the option to do so, so the choice was made to use a
-//! System V semaphore rather than a POSIX semaphore.
+//! System V semaphore rather than a POSIX semaphore by default.
 //!
 //! System V semaphores are interesting in that they have an unusual
 //! initialization procedure where a semaphore is created and *then*
@@ -15,22 +15,134 @@
 //! Additionally all semaphores need a `key_t` which originates from an actual
 //! existing file, so this implementation ensures that a file exists when
 //! creating a semaphore.
+//!
+//! Some environments (containers, macOS) ship System V with tiny `SEMMNI`/
+//! `SEMMNS` kernel limits, so [`Backend::PosixNamed`] is available as an
+//! opt-in escape hatch backed by `sem_open` instead.
+//!
+//! Within a process, opening the same System V semaphore `name` twice is
+//! deduplicated to a single shared handle, keyed off the `(st_dev, st_ino)`
+//! of the resolved key file rather than the name string itself.
 
 #![allow(bad_style)]
 
 use libc::{sembuf, EEXIST, O_RDWR};
+use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::hash::{Hash, Hasher};
 use std::io::{Error, ErrorKind, Result};
 use std::mem;
+use std::os::unix::fs::MetadataExt;
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::time::{Duration, Instant};
 
 use self::consts::{semid_ds, SEM_UNDO, SETVAL};
 use std::collections::hash_map::DefaultHasher;
 
-pub struct Semaphore {
+/// Selects which kernel primitive a [`Semaphore`] is backed by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    /// System V semaphores (`semget`/`semop`). The default: this is what
+    /// gives [`unlink`] and `Drop` their crash-cleanup semantics.
+    SystemV,
+    /// POSIX named semaphores (`sem_open`). Initializes atomically and
+    /// avoids System V's kernel limits. Unlinked via `sem_unlink` through
+    /// [`unlink_with_backend`].
+    PosixNamed,
+}
+
+impl Default for Backend {
+    fn default() -> Backend {
+        Backend::SystemV
+    }
+}
+
+pub struct Semaphore(Repr);
+
+enum Repr {
+    SystemV(Arc<SystemV>),
+    PosixNamed(posix::Semaphore),
+}
+
+struct SystemV {
     semid: libc::c_int,
+    /// The exact key file resolved when this handle was opened, so `Drop`
+    /// removes the file this handle actually owns rather than re-deriving a
+    /// path from `name`, which can resolve to a different (recreated) file
+    /// by the time `Drop` runs.
+    key_file: PathBuf,
+    inode: InodeKey,
+}
+
+/// Identifies the on-disk key file a `SystemV` handle was opened through, so
+/// that a second `Semaphore::new` for the same `name` (and so the same
+/// underlying file) can be recognized as "the same semaphore". The key file
+/// name embeds a hash of the exact `name` string, so differently spelled
+/// names never share a key file.
+type InodeKey = (libc::dev_t, libc::ino_t);
+
+/// Process-local table of currently-open System V handles, keyed by the
+/// `(st_dev, st_ino)` of their key file. Holding only a `Weak` means a name
+/// whose last handle has been dropped doesn't keep its entry alive, so a
+/// fresh `semget` naturally happens the next time it's opened.
+fn registry() -> &'static Mutex<HashMap<InodeKey, Weak<SystemV>>> {
+    static REGISTRY: OnceLock<Mutex<HashMap<InodeKey, Weak<SystemV>>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Marks `name` so that no new `Semaphore::new`/`with_backend` call can
+/// attach to its current kernel object. On the System V backend this also
+/// removes the semaphore set itself (see [`unlink_with_backend`]), so handles
+/// that are already open, in this or any other process, fail with
+/// `EIDRM`/`EINVAL` from then on; only [`Backend::PosixNamed`] handles keep
+/// working until they're dropped. Equivalent to
+/// `unlink_with_backend(name, Backend::default())`; use
+/// [`unlink_with_backend`] to unlink a [`Backend::PosixNamed`] semaphore.
+///
+/// # Safety
+///
+/// The caller must ensure no other thread is concurrently calling
+/// `Semaphore::new(name, ..)`, or the removal race could let a new opener
+/// attach to the set being unlinked.
+pub unsafe fn unlink(name: &str) {
+    unlink_with_backend(name, Backend::default())
+}
+
+/// Like [`unlink`], but for a specific [`Backend`].
+///
+/// On the System V backend this also `IPC_RMID`s the semaphore set the key
+/// file currently resolves to (if any), so the kernel object is released
+/// even if this process holds no open handle for `name`. The POSIX backend
+/// has no equivalent "no handle open" case: `sem_unlink` always reaches the
+/// kernel object directly by name.
+///
+/// # Safety
+///
+/// Same caveats as [`unlink`].
+pub unsafe fn unlink_with_backend(name: &str, backend: Backend) {
+    match backend {
+        Backend::SystemV => {
+            let filename = SystemV::filename(name);
+            // Best-effort: if the key file this name resolves to still
+            // exists, ftok it and RMID the semaphore set it identifies
+            // before removing the file, so no in-process open handle is
+            // required for `unlink` to release the kernel object. `ftok`
+            // simply fails if the file is already gone, so no existence
+            // check is needed up front.
+            let path = filename.to_str().unwrap().to_string() + "\0";
+            let key = libc::ftok(path.as_ptr() as *const libc::c_char, 'I' as libc::c_int);
+            if key != -1 {
+                let semid = libc::semget(key, 0, 0);
+                if semid >= 0 {
+                    libc::semctl(semid, 0, libc::IPC_RMID);
+                }
+            }
+            let _ = fs::remove_file(filename);
+        }
+        Backend::PosixNamed => posix::unlink(name),
+    }
 }
 
 #[cfg(target_os = "linux")]
@@ -60,9 +172,19 @@ mod consts {
     pub type semid_ds = libc::semid_ds;
 }
 
-impl Semaphore {
-    pub unsafe fn new(name: &str, cnt: usize) -> Result<Semaphore> {
-        let key = Semaphore::key(name)?;
+impl SystemV {
+    unsafe fn new(name: &str, cnt: usize) -> Result<Arc<SystemV>> {
+        let (key, key_file, inode) = SystemV::key_and_inode(name)?;
+
+        // Opening the same name twice in this process should yield the same
+        // handle rather than a second independent one over the same kernel
+        // object, so a hit here skips `semget` entirely. The lock is held for
+        // the rest of this function to close the race between the check and
+        // the insert below.
+        let mut reg = registry().lock().unwrap();
+        if let Some(existing) = reg.get(&inode).and_then(Weak::upgrade) {
+            return Ok(existing);
+        }
 
         // System V semaphores cannot be initialized at creation, and we don't
         // know which process is responsible for creating the semaphore, so we
@@ -127,7 +249,13 @@ impl Semaphore {
         }
 
         // Phew! That took long enough...
-        Ok(Semaphore { semid })
+        let shared = Arc::new(SystemV {
+            semid,
+            key_file,
+            inode,
+        });
+        reg.insert(inode, Arc::downgrade(&shared));
+        Ok(shared)
     }
 
     /// Get value hash
@@ -147,16 +275,26 @@ impl Semaphore {
         env::temp_dir().join("ipc-rs-sems").join(format!(
             "{}-{}",
             filename,
-            Semaphore::hash::<_>(&(name, "ipc-rs"))
+            SystemV::hash::<_>(&(name, "ipc-rs"))
         ))
     }
 
+    /// Like `key`, but also returns the key file's path and the `(st_dev,
+    /// st_ino)` of the resolved key file, which is what identifies "the same
+    /// semaphore" for the in-process dedup registry.
+    unsafe fn key_and_inode(name: &str) -> Result<(libc::key_t, PathBuf, InodeKey)> {
+        let key = SystemV::key(name)?;
+        let key_file = SystemV::filename(name);
+        let meta = fs::metadata(&key_file)?;
+        Ok((key, key_file, (meta.dev(), meta.ino())))
+    }
+
     /// Generate the `key_t` from `ftok` which will be passed to `semget`.
     ///
     /// This function will ensure that the relevant file is located on the
     /// filesystem and will then invoke ftok on it.
     unsafe fn key(name: &str) -> Result<libc::key_t> {
-        let filename = Semaphore::filename(name);
+        let filename = SystemV::filename(name);
         let dir = filename.parent().unwrap();
 
         // As long as someone creates the directory we're alright.
@@ -191,35 +329,93 @@ impl Semaphore {
         }
     }
 
-    pub unsafe fn wait(&self) {
+    unsafe fn wait(&self) -> Result<()> {
         loop {
             if self.modify(-1, true) == 0 {
-                return;
+                return Ok(());
             }
 
             match Error::last_os_error() {
                 ref e if e.raw_os_error() == Some(libc::EINTR) => {}
-                e => panic!("unknown wait error: {}", e),
+                e => return Err(e),
             }
         }
     }
 
-    pub unsafe fn try_wait(&self) -> bool {
+    unsafe fn try_wait(&self) -> Result<bool> {
         if self.modify(-1, false) == 0 {
-            return true;
+            return Ok(true);
         }
 
         match Error::last_os_error() {
-            ref e if e.raw_os_error() == Some(libc::EAGAIN) => false,
-            e => panic!("unknown try_wait error: {}", e),
+            ref e if e.raw_os_error() == Some(libc::EAGAIN) => Ok(false),
+            e => Err(e),
         }
     }
 
-    pub unsafe fn post(&self) {
+    unsafe fn post(&self) -> Result<()> {
         if self.modify(1, true) == 0 {
-            return;
+            Ok(())
+        } else {
+            Err(Error::last_os_error())
+        }
+    }
+
+    /// Blocks until the semaphore can be acquired or `timeout` elapses,
+    /// returning `true` if it was acquired and `false` on timeout. Returns
+    /// an error on `EIDRM`/`EINVAL`, e.g. if another process `unlink`ed the
+    /// semaphore while this call was blocked.
+    unsafe fn wait_timeout(&self, timeout: Duration) -> Result<bool> {
+        self.timed_wait(timeout)
+    }
+
+    #[cfg(target_os = "linux")]
+    unsafe fn timed_wait(&self, timeout: Duration) -> Result<bool> {
+        let mut remaining = timeout;
+        loop {
+            let ts = libc::timespec {
+                tv_sec: remaining.as_secs() as libc::time_t,
+                tv_nsec: libc::c_long::from(remaining.subsec_nanos() as i32),
+            };
+            let mut buf = sembuf {
+                sem_num: 0,
+                sem_op: -1,
+                sem_flg: SEM_UNDO,
+            };
+            let start = Instant::now();
+            if libc::semtimedop(self.semid, &mut buf, 1, &ts) == 0 {
+                return Ok(true);
+            }
+
+            match Error::last_os_error() {
+                ref e if e.raw_os_error() == Some(libc::EAGAIN) => return Ok(false),
+                ref e if e.raw_os_error() == Some(libc::EINTR) => {
+                    let elapsed = start.elapsed();
+                    if elapsed >= remaining {
+                        return Ok(false);
+                    }
+                    remaining -= elapsed;
+                }
+                e => return Err(e),
+            }
+        }
+    }
+
+    // macOS does not implement `semtimedop`, so the timeout is approximated
+    // by polling `try_wait` until the deadline passes.
+    #[cfg(target_os = "macos")]
+    unsafe fn timed_wait(&self, timeout: Duration) -> Result<bool> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if self.try_wait()? {
+                return Ok(true);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(false);
+            }
+            std::thread::sleep(Duration::from_millis(1).min(deadline - now));
         }
-        panic!("unknown post error: {}", Error::last_os_error())
     }
 
     unsafe fn modify(&self, amt: i16, wait: bool) -> libc::c_int {
@@ -236,8 +432,374 @@ impl Semaphore {
     }
 }
 
-impl Drop for Semaphore {
-    fn drop(&mut self) {}
+impl Drop for SystemV {
+    fn drop(&mut self) {
+        // This only runs once the last `Arc<SystemV>` (i.e. the last open
+        // handle for this name/inode in the process) is gone. But another
+        // thread's `SystemV::new` can race us: it locks the registry before
+        // we do, finds our entry's `Weak` no longer upgradeable (our strong
+        // count already hit 0), and re-attaches to the same still-present
+        // key file, replacing our entry with its own. If that happened, the
+        // registry no longer points at us, and cleanup belongs to that new
+        // handle instead of us.
+        let mut reg = registry().lock().unwrap();
+        let still_ours = reg
+            .get(&self.inode)
+            .map_or(false, |weak| weak.as_ptr() == self as *const SystemV);
+        if !still_ours {
+            return;
+        }
+        reg.remove(&self.inode);
+        drop(reg);
+        unsafe {
+            libc::semctl(self.semid, 0, libc::IPC_RMID);
+            // Only remove the key file if it's still the one this handle
+            // opened, not one recreated at the same path since.
+            let same_file = fs::metadata(&self.key_file)
+                .map_or(false, |m| (m.dev(), m.ino()) == self.inode);
+            if same_file {
+                let _ = fs::remove_file(&self.key_file);
+            }
+        }
+    }
+}
+
+impl Semaphore {
+    pub unsafe fn new(name: &str, cnt: usize) -> Result<Semaphore> {
+        Semaphore::with_backend(name, cnt, Backend::default())
+    }
+
+    pub unsafe fn with_backend(name: &str, cnt: usize, backend: Backend) -> Result<Semaphore> {
+        match backend {
+            Backend::SystemV => SystemV::new(name, cnt).map(|s| Semaphore(Repr::SystemV(s))),
+            Backend::PosixNamed => {
+                posix::Semaphore::new(name, cnt).map(|s| Semaphore(Repr::PosixNamed(s)))
+            }
+        }
+    }
+
+    /// Blocks until the semaphore can be acquired.
+    ///
+    /// Returns an error if the underlying kernel object disappears out from
+    /// under this handle, e.g. `EIDRM`/`EINVAL` on the System V backend once
+    /// another process has `unlink`ed it.
+    pub unsafe fn wait(&self) -> Result<()> {
+        match &self.0 {
+            Repr::SystemV(s) => s.wait(),
+            Repr::PosixNamed(s) => s.wait(),
+        }
+    }
+
+    pub unsafe fn try_wait(&self) -> Result<bool> {
+        match &self.0 {
+            Repr::SystemV(s) => s.try_wait(),
+            Repr::PosixNamed(s) => s.try_wait(),
+        }
+    }
+
+    pub unsafe fn post(&self) -> Result<()> {
+        match &self.0 {
+            Repr::SystemV(s) => s.post(),
+            Repr::PosixNamed(s) => s.post(),
+        }
+    }
+
+    /// Blocks until the semaphore can be acquired or `timeout` elapses,
+    /// returning `true` if it was acquired and `false` on timeout. Returns
+    /// an error if the underlying kernel object disappears out from under
+    /// this handle, e.g. `EIDRM`/`EINVAL` on the System V backend once
+    /// another process has `unlink`ed it.
+    pub unsafe fn wait_timeout(&self, timeout: Duration) -> Result<bool> {
+        match &self.0 {
+            Repr::SystemV(s) => s.wait_timeout(timeout),
+            Repr::PosixNamed(s) => s.wait_timeout(timeout),
+        }
+    }
+}
+
+/// The POSIX `sem_open` backend selected via `Backend::PosixNamed`.
+///
+/// Unlike the System V path above, `sem_open` initializes atomically, so
+/// there's no `ftok` key-file dance or `sem_otime` spin loop here, and
+/// `sem_close` in `Drop` is all the cleanup a handle needs.
+mod posix {
+    use std::ffi::CString;
+    use std::io::{Error, Result};
+    use std::time::Duration;
+
+    pub struct Semaphore {
+        sem: *mut libc::sem_t,
+    }
+
+    unsafe impl Send for Semaphore {}
+    unsafe impl Sync for Semaphore {}
+
+    impl Semaphore {
+        pub unsafe fn new(name: &str, cnt: usize) -> Result<Semaphore> {
+            let name = Semaphore::sanitize(name);
+            let sem = libc::sem_open(name.as_ptr(), libc::O_CREAT, 0o666, cnt as libc::c_uint);
+            if sem == libc::SEM_FAILED {
+                Err(Error::last_os_error())
+            } else {
+                Ok(Semaphore { sem })
+            }
+        }
+
+        /// POSIX named semaphores live in a flat namespace where the name
+        /// must start with exactly one leading slash and contain no others.
+        fn sanitize(name: &str) -> CString {
+            let name = format!("/{}", name.replace('/', ""));
+            CString::new(name).expect("semaphore name must not contain NUL bytes")
+        }
+
+        pub unsafe fn wait(&self) -> Result<()> {
+            loop {
+                if libc::sem_wait(self.sem) == 0 {
+                    return Ok(());
+                }
+                match Error::last_os_error() {
+                    ref e if e.raw_os_error() == Some(libc::EINTR) => {}
+                    e => return Err(e),
+                }
+            }
+        }
+
+        pub unsafe fn try_wait(&self) -> Result<bool> {
+            if libc::sem_trywait(self.sem) == 0 {
+                return Ok(true);
+            }
+            match Error::last_os_error() {
+                ref e if e.raw_os_error() == Some(libc::EAGAIN) => Ok(false),
+                e => Err(e),
+            }
+        }
+
+        pub unsafe fn post(&self) -> Result<()> {
+            if libc::sem_post(self.sem) == 0 {
+                Ok(())
+            } else {
+                Err(Error::last_os_error())
+            }
+        }
+
+        /// Blocks until the semaphore can be acquired or `timeout` elapses,
+        /// returning `true` if it was acquired and `false` on timeout.
+        #[cfg(target_os = "linux")]
+        pub unsafe fn wait_timeout(&self, timeout: Duration) -> Result<bool> {
+            let mut ts: libc::timespec = std::mem::zeroed();
+            libc::clock_gettime(libc::CLOCK_REALTIME, &mut ts);
+            ts.tv_sec += timeout.as_secs() as libc::time_t;
+            ts.tv_nsec += libc::c_long::from(timeout.subsec_nanos() as i32);
+            if ts.tv_nsec >= 1_000_000_000 {
+                ts.tv_nsec -= 1_000_000_000;
+                ts.tv_sec += 1;
+            }
+
+            loop {
+                if libc::sem_timedwait(self.sem, &ts) == 0 {
+                    return Ok(true);
+                }
+                match Error::last_os_error() {
+                    ref e if e.raw_os_error() == Some(libc::ETIMEDOUT) => return Ok(false),
+                    ref e if e.raw_os_error() == Some(libc::EINTR) => {}
+                    e => return Err(e),
+                }
+            }
+        }
+
+        // macOS does not implement `sem_timedwait`, so the timeout is
+        // approximated by polling `try_wait` until the deadline passes.
+        #[cfg(target_os = "macos")]
+        pub unsafe fn wait_timeout(&self, timeout: Duration) -> Result<bool> {
+            use std::time::Instant;
+
+            let deadline = Instant::now() + timeout;
+            loop {
+                if self.try_wait()? {
+                    return Ok(true);
+                }
+                let now = Instant::now();
+                if now >= deadline {
+                    return Ok(false);
+                }
+                std::thread::sleep(Duration::from_millis(1).min(deadline - now));
+            }
+        }
+    }
+
+    impl Drop for Semaphore {
+        fn drop(&mut self) {
+            unsafe {
+                libc::sem_close(self.sem);
+            }
+        }
+    }
+
+    /// Removes `name` from the kernel's POSIX semaphore namespace. Unlike
+    /// the System V backend, this reaches the kernel object directly by
+    /// name, so it releases it even if this process holds no open handle
+    /// for `name`.
+    pub unsafe fn unlink(name: &str) {
+        let name = Semaphore::sanitize(name);
+        libc::sem_unlink(name.as_ptr());
+    }
+}
+
+/// A set of `n` System V semaphores sharing a single `semid`, which allows
+/// [`SemaphoreSet::op`] to apply operations across several of them in one
+/// atomic `semop` call. This is the feature that sets System V semaphores
+/// apart from the single-counter [`Semaphore`] above, and is what makes
+/// producer/consumer or reader/writer coordination possible without
+/// emulating it via a sequence of single-semaphore waits/posts.
+///
+/// Like `Semaphore`, opening the same `name` twice in this process is
+/// deduplicated to a single shared handle via an inode-keyed registry, so
+/// `Drop` only `IPC_RMID`s the set once the last handle is gone.
+pub struct SemaphoreSet(Arc<SemaphoreSetInner>);
+
+struct SemaphoreSetInner {
+    semid: libc::c_int,
+    key_file: PathBuf,
+    inode: InodeKey,
+}
+
+/// Process-local table of currently-open `SemaphoreSet` handles, mirroring
+/// `registry()` above but holding `SemaphoreSetInner` handles. The two
+/// registries are separate, but a `Semaphore` and a `SemaphoreSet` opened
+/// with the same `name` share a key file and so a `key_t`: they reach the
+/// same kernel set (or fail to attach if its size is smaller than `n`).
+fn set_registry() -> &'static Mutex<HashMap<InodeKey, Weak<SemaphoreSetInner>>> {
+    static REGISTRY: OnceLock<Mutex<HashMap<InodeKey, Weak<SemaphoreSetInner>>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+impl SemaphoreSet {
+    pub unsafe fn new(name: &str, n: usize) -> Result<SemaphoreSet> {
+        let (key, key_file, inode) = SystemV::key_and_inode(name)?;
+
+        let mut reg = set_registry().lock().unwrap();
+        if let Some(existing) = reg.get(&inode).and_then(Weak::upgrade) {
+            return Ok(SemaphoreSet(existing));
+        }
+
+        // Same non-atomic create-then-initialize dance as `Semaphore::new`,
+        // except every semaphore in the set needs its own `SETVAL` before the
+        // `sem_otime`-bump that signals "initialization is done" to anyone
+        // spinning on `IPC_STAT`.
+        let mut semid = libc::semget(
+            key,
+            n as libc::c_int,
+            libc::IPC_CREAT | libc::IPC_EXCL | 0o666,
+        );
+        if semid >= 0 {
+            for i in 0..n {
+                if libc::semctl(semid, i as libc::c_int, SETVAL, 0) != 0 {
+                    let err = Error::last_os_error();
+                    libc::semctl(semid, 0, libc::IPC_RMID);
+                    return Err(err);
+                }
+            }
+            // A no-op semop still bumps `sem_otime`, which is all we need
+            // since every semaphore was just clamped to 0 above.
+            let mut buf = sembuf {
+                sem_num: 0,
+                sem_op: 0,
+                sem_flg: 0,
+            };
+            if libc::semop(semid, &mut buf, 1) != 0 {
+                let err = Error::last_os_error();
+                libc::semctl(semid, 0, libc::IPC_RMID);
+                return Err(err);
+            }
+        } else {
+            match Error::last_os_error() {
+                ref e if e.raw_os_error() == Some(EEXIST) => {
+                    semid = libc::semget(key, n as libc::c_int, 0);
+                    if semid < 0 {
+                        return Err(Error::last_os_error());
+                    }
+
+                    let mut ok = false;
+                    for _ in 0..1000 {
+                        let mut buf: semid_ds = mem::zeroed();
+                        if libc::semctl(semid, 0, libc::IPC_STAT, &mut buf) != 0 {
+                            return Err(Error::last_os_error());
+                        }
+                        if buf.sem_otime != 0 {
+                            ok = true;
+                            break;
+                        }
+                    }
+                    if !ok {
+                        return Err(Error::new(
+                            ErrorKind::TimedOut,
+                            "timed out waiting for sem set to be initialized",
+                        ));
+                    }
+                }
+                e => return Err(e),
+            }
+        }
+
+        // Phew! That took long enough...
+        let shared = Arc::new(SemaphoreSetInner {
+            semid,
+            key_file,
+            inode,
+        });
+        reg.insert(inode, Arc::downgrade(&shared));
+        Ok(SemaphoreSet(shared))
+    }
+
+    /// Atomically applies a batch of `(index, delta)` operations: the kernel
+    /// either applies every operation in `ops` or blocks until it can apply
+    /// all of them, which single-semaphore `wait`/`post` calls in a loop
+    /// cannot guarantee.
+    ///
+    /// Returns an error on `EIDRM`/`EINVAL`, e.g. if another process removed
+    /// the set while this call was blocked.
+    pub unsafe fn op(&self, ops: &[(usize, i16)]) -> Result<()> {
+        let mut bufs: Vec<sembuf> = ops
+            .iter()
+            .map(|&(index, delta)| sembuf {
+                sem_num: index as libc::c_ushort,
+                sem_op: delta,
+                sem_flg: SEM_UNDO,
+            })
+            .collect();
+        loop {
+            if libc::semop(self.0.semid, bufs.as_mut_ptr(), bufs.len()) == 0 {
+                return Ok(());
+            }
+
+            match Error::last_os_error() {
+                ref e if e.raw_os_error() == Some(libc::EINTR) => {}
+                e => return Err(e),
+            }
+        }
+    }
+}
+
+impl Drop for SemaphoreSetInner {
+    fn drop(&mut self) {
+        // Same "only clean up if the registry still points at us" race guard
+        // as `Drop for SystemV`: a concurrent `SemaphoreSet::new` for the
+        // same key file may have already replaced our registry entry with
+        // its own live handle, in which case cleanup belongs to it instead.
+        let mut reg = set_registry().lock().unwrap();
+        let still_ours = reg
+            .get(&self.inode)
+            .map_or(false, |weak| weak.as_ptr() == self as *const SemaphoreSetInner);
+        if !still_ours {
+            return;
+        }
+        reg.remove(&self.inode);
+        drop(reg);
+        unsafe {
+            libc::semctl(self.semid, 0, libc::IPC_RMID);
+            let _ = fs::remove_file(&self.key_file);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -249,10 +811,84 @@ mod tests {
     use std::mem;
     use std::process::Command;
     use std::str;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
 
     use super::consts::semid_ds;
+    use super::{Backend, Semaphore, SemaphoreSet, SystemV};
     use tempdir::TempDir;
 
+    /// Gives each test its own semaphore name, scoped by pid so concurrent
+    /// test runs on the same machine don't collide either.
+    fn unique_name(tag: &str) -> String {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        format!(
+            "ipc-rs-test-{}-{}-{}",
+            std::process::id(),
+            tag,
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        )
+    }
+
+    #[test]
+    fn systemv_new_dedups_same_name() {
+        let name = unique_name("dedup");
+        unsafe {
+            let a = SystemV::new(&name, 1).unwrap();
+            let b = SystemV::new(&name, 1).unwrap();
+            assert!(Arc::ptr_eq(&a, &b));
+            super::unlink(&name);
+        }
+    }
+
+    #[test]
+    fn wait_timeout_times_out() {
+        let name = unique_name("timeout");
+        unsafe {
+            let sem = Semaphore::new(&name, 0).unwrap();
+            assert!(!sem.wait_timeout(Duration::from_millis(50)).unwrap());
+            super::unlink(&name);
+        }
+    }
+
+    #[test]
+    fn semaphore_set_op_round_trip() {
+        let name = unique_name("set");
+        unsafe {
+            let set = SemaphoreSet::new(&name, 2).unwrap();
+            set.op(&[(0, 1), (1, 1)]).unwrap();
+            set.op(&[(0, -1), (1, -1)]).unwrap();
+            super::unlink(&name);
+        }
+    }
+
+    #[test]
+    fn posix_backend_round_trip() {
+        let name = unique_name("posix");
+        unsafe {
+            let sem = Semaphore::with_backend(&name, 1, Backend::PosixNamed).unwrap();
+            assert!(sem.try_wait().unwrap());
+            sem.post().unwrap();
+            sem.wait().unwrap();
+            super::unlink_with_backend(&name, Backend::PosixNamed);
+        }
+    }
+
+    #[test]
+    fn drop_releases_kernel_semaphore() {
+        let name = unique_name("drop");
+        let semid = unsafe {
+            let sem = SystemV::new(&name, 1).unwrap();
+            sem.semid
+        };
+        // `sem`'s only `Arc` was dropped above, so the kernel set should
+        // already be gone: `IPC_STAT`ing the old `semid` must now fail.
+        let mut buf: semid_ds = unsafe { mem::zeroed() };
+        let rc = unsafe { libc::semctl(semid, 0, libc::IPC_STAT, &mut buf) };
+        assert_eq!(rc, -1);
+    }
+
     macro_rules! offset {
         ($ty:ty, $f:ident) => {
             unsafe {
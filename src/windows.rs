@@ -2,12 +2,14 @@ use libc;
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 use std::i32;
-use std::io::{Error, Result};
+use std::io::{Error, ErrorKind, Result};
+use std::time::Duration;
 
 pub struct Semaphore {
     handle: libc::HANDLE,
 }
 
+pub const WAIT_ABANDONED: libc::DWORD = 0x00000080;
 pub const WAIT_FAILED: libc::DWORD = 0xFFFFFFFF;
 pub const WAIT_TIMEOUT: libc::DWORD = 0x00000102;
 
@@ -23,6 +25,12 @@ extern "system" {
         lReleaseCount: libc::LONG,
         lpPreviousCount: *mut libc::LONG,
     ) -> libc::BOOL;
+    fn WaitForMultipleObjects(
+        nCount: libc::DWORD,
+        lpHandles: *const libc::HANDLE,
+        bWaitAll: libc::BOOL,
+        dwMilliseconds: libc::DWORD,
+    ) -> libc::DWORD;
 }
 
 impl Semaphore {
@@ -54,30 +62,65 @@ impl Semaphore {
         }
     }
 
-    pub unsafe fn wait(&self) {
+    /// Blocks until the semaphore can be acquired.
+    ///
+    /// Returns an error on `WAIT_ABANDONED`/`WAIT_FAILED`, e.g. if the
+    /// semaphore's owning process died while holding it.
+    pub unsafe fn wait(&self) -> Result<()> {
         match libc::WaitForSingleObject(self.handle, libc::INFINITE) {
-            libc::WAIT_OBJECT_0 => {}
-            WAIT_FAILED => panic!("failed to wait: {}", Error::last_os_error()),
-            n => panic!("bad wait(): {}/{}", n, Error::last_os_error()),
+            libc::WAIT_OBJECT_0 => Ok(()),
+            WAIT_ABANDONED | WAIT_FAILED => Err(Error::last_os_error()),
+            n => Err(Error::new(
+                ErrorKind::Other,
+                format!("bad wait(): {}/{}", n, Error::last_os_error()),
+            )),
         }
     }
 
-    pub unsafe fn try_wait(&self) -> bool {
+    pub unsafe fn try_wait(&self) -> Result<bool> {
         match libc::WaitForSingleObject(self.handle, 0) {
-            libc::WAIT_OBJECT_0 => true,
-            WAIT_TIMEOUT => false,
-            WAIT_FAILED => panic!("failed to wait: {}", Error::last_os_error()),
-            n => panic!("bad wait(): {}/{}", n, Error::last_os_error()),
+            libc::WAIT_OBJECT_0 => Ok(true),
+            WAIT_TIMEOUT => Ok(false),
+            WAIT_ABANDONED | WAIT_FAILED => Err(Error::last_os_error()),
+            n => Err(Error::new(
+                ErrorKind::Other,
+                format!("bad wait(): {}/{}", n, Error::last_os_error()),
+            )),
         }
     }
 
-    pub unsafe fn post(&self) {
+    pub unsafe fn post(&self) -> Result<()> {
         if let 0 = ReleaseSemaphore(self.handle, 1, std::ptr::null_mut()) {
-            panic!("failed to release semaphore: {}", Error::last_os_error())
+            Err(Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Blocks until the semaphore can be acquired or `timeout` elapses,
+    /// returning `true` if it was acquired and `false` on timeout.
+    ///
+    /// Returns an error on `WAIT_ABANDONED`/`WAIT_FAILED`, e.g. if the
+    /// semaphore's owning process died while holding it.
+    pub unsafe fn wait_timeout(&self, timeout: Duration) -> Result<bool> {
+        let millis = timeout.as_millis().min(u128::from(libc::INFINITE - 1)) as libc::DWORD;
+        match libc::WaitForSingleObject(self.handle, millis) {
+            libc::WAIT_OBJECT_0 => Ok(true),
+            WAIT_TIMEOUT => Ok(false),
+            WAIT_ABANDONED | WAIT_FAILED => Err(Error::last_os_error()),
+            n => Err(Error::new(
+                ErrorKind::Other,
+                format!("bad wait(): {}/{}", n, Error::last_os_error()),
+            )),
         }
     }
 }
 
+/// No-op on Windows: semaphore handles are already reference-counted by the
+/// kernel, so `Drop`'s `CloseHandle` is sufficient to release the object once
+/// every handle is gone.
+pub unsafe fn unlink(_name: &str) {}
+
 unsafe impl Send for Semaphore {}
 unsafe impl Sync for Semaphore {}
 
@@ -88,3 +131,93 @@ impl Drop for Semaphore {
         }
     }
 }
+
+/// A set of `n` named semaphore handles.
+///
+/// Windows has no equivalent of System V's atomic group `semop`, so
+/// [`SemaphoreSet::op`] is **not atomic**: it waits on every handle with a
+/// negative delta via a single `WaitForMultipleObjects` call (so each such
+/// delta must be exactly `-1`, since a handle cannot appear twice in that
+/// call), then applies the positive deltas in order with `ReleaseSemaphore`.
+/// The waits and the releases are therefore not one step: other threads can
+/// observe the set between them, and a failing `ReleaseSemaphore` returns an
+/// error with the waits (and any earlier releases) already applied, neither
+/// of which can happen on the System V backend.
+pub struct SemaphoreSet {
+    handles: Vec<libc::HANDLE>,
+}
+
+impl SemaphoreSet {
+    pub unsafe fn new(name: &str, n: usize) -> Result<SemaphoreSet> {
+        let mut handles = Vec::with_capacity(n);
+        for i in 0..n {
+            match Semaphore::new(&format!("{}-{}", name, i), 0) {
+                Ok(sem) => {
+                    handles.push(sem.handle);
+                    // The handle is now owned by `handles`; don't let
+                    // `Semaphore`'s `Drop` close it out from under us.
+                    std::mem::forget(sem);
+                }
+                Err(err) => {
+                    for handle in handles {
+                        libc::CloseHandle(handle);
+                    }
+                    return Err(err);
+                }
+            }
+        }
+        Ok(SemaphoreSet { handles })
+    }
+
+    pub unsafe fn op(&self, ops: &[(usize, i16)]) -> Result<()> {
+        let mut waits = Vec::new();
+        for &(index, delta) in ops {
+            if delta < 0 {
+                if delta != -1 {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        "Windows semaphore sets can only wait one at a time",
+                    ));
+                }
+                waits.push(self.handles[index]);
+            }
+        }
+        if !waits.is_empty() {
+            match WaitForMultipleObjects(waits.len() as libc::DWORD, waits.as_ptr(), 1, libc::INFINITE)
+            {
+                n if n < libc::WAIT_OBJECT_0 + waits.len() as libc::DWORD => {}
+                WAIT_ABANDONED | WAIT_FAILED => return Err(Error::last_os_error()),
+                n => {
+                    return Err(Error::new(
+                        ErrorKind::Other,
+                        format!("bad wait(): {}/{}", n, Error::last_os_error()),
+                    ))
+                }
+            }
+        }
+
+        for &(index, delta) in ops {
+            if delta > 0 {
+                if let 0 =
+                    ReleaseSemaphore(self.handles[index], delta as libc::LONG, std::ptr::null_mut())
+                {
+                    return Err(Error::last_os_error());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+unsafe impl Send for SemaphoreSet {}
+unsafe impl Sync for SemaphoreSet {}
+
+impl Drop for SemaphoreSet {
+    fn drop(&mut self) {
+        for &handle in &self.handles {
+            unsafe {
+                libc::CloseHandle(handle);
+            }
+        }
+    }
+}